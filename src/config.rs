@@ -18,7 +18,7 @@
 use std::{env, fs::File, path::PathBuf};
 
 // third party dependencies
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use dirs::home_dir;
 use ron::de::from_reader;
 use serde::Deserialize;
@@ -57,6 +57,10 @@ pub struct Git {
     /// Max number of worktrees created at once
     #[serde(default = "default_workers")]
     pub workers: usize,
+    /// Base branches to apply series on, tried in order until one applies cleanly.
+    /// Defaults to `["master"]`
+    #[serde(default = "default_branches")]
+    pub branches: Vec<String>,
 }
 
 fn default_public_key() -> PathBuf {
@@ -81,6 +85,10 @@ fn default_workers() -> usize {
     1
 }
 
+fn default_branches() -> Vec<String> {
+    vec![String::from("master")]
+}
+
 /// Defines the Patchwork server you wish to work with.
 /// Credentials are not necessary unless you wish to push results.
 /// snowpatch only supports API token authentication and not Basic Auth.
@@ -107,6 +115,25 @@ pub enum Runner {
         url: Url,
         token: Option<String>,
     },
+    /// Runs commands directly in the git worktree, no remote required.
+    Local {
+        commands: Vec<LocalCommand>,
+        /// Directory to write command output to.  Output is only logged if unset.
+        #[serde(default)]
+        log_dir: Option<PathBuf>,
+    },
+}
+
+/// A single command for the local runner, reported as its own check.
+#[derive(Debug, Deserialize, Clone)]
+pub struct LocalCommand {
+    /// Name of the job, used in the Patchwork check context
+    pub name: String,
+    /// Command to run through `sh -c` from the root of the worktree
+    pub command: String,
+    /// Seconds to wait before killing the command.  Waits forever if unset.
+    #[serde(default)]
+    pub timeout: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -120,6 +147,10 @@ fn validate_config(config: &Config) -> Result<()> {
     File::open(&config.git.public_key).context("Couldn't open public key file")?;
     File::open(&config.git.private_key).context("Couldn't open private key file")?;
 
+    if config.git.branches.is_empty() {
+        bail!("Need at least one base branch to apply series on");
+    }
+
     Ok(())
 }
 
@@ -184,6 +215,7 @@ mod tests {
                 repo: "/home/ruscur/code/linux".to_owned(),
                 workdir: "/home/ruscur/code/snowpatch/workdir".to_owned(),
                 workers: 2,
+                branches: default_branches(),
             },
             patchwork: Patchwork {
                 url: Url::parse("https://patchwork.ozlabs.org").unwrap(),
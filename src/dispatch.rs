@@ -34,13 +34,25 @@ impl Dispatch {
 
                 let context: String = format!("{}-{}", handle, job_name);
 
+                // Let people know which tree it was tested on.
+                let description = match DB.open_tree(b"series branch")?.get(series)? {
+                    Some(branch) => {
+                        let branch = String::from_utf8_lossy(&branch);
+                        match job_result.description {
+                            Some(d) => Some(format!("{} (applied on {})", d, branch)),
+                            None => Some(format!("Applied on {}", branch)),
+                        }
+                    }
+                    None => job_result.description,
+                };
+
                 let check_to_send = TestResult {
                     state: job_result.outcome,
                     target_url: match job_result.url {
                         Some(url) => Some(url.to_string()),
                         None => None,
                     },
-                    description: job_result.description,
+                    description,
                     context: Some(context),
                 };
 
@@ -7,9 +7,12 @@ use git2::Commit;
 use git2::Cred;
 use git2::Diff;
 use git2::ObjectType;
+use git2::Oid;
 use git2::PushOptions;
 use git2::RemoteCallbacks;
 use git2::Repository;
+use git2::Signature;
+use git2::Time;
 use git2::Worktree;
 use git2::WorktreePruneOptions;
 use log::debug;
@@ -29,16 +32,27 @@ use url::Url;
 
 use crate::database::*;
 use crate::patchwork::*;
+use crate::runner::Runner;
 use crate::DB;
 
 pub struct GitOps {
     repo: Repository,
     pool: ThreadPool,
     workdir: PathBuf,
+    /// Base branches to try applying series on, in order.
+    branches: Vec<String>,
+    /// Runners that need to be run while we still have the worktree.
+    runners: Vec<Box<dyn Runner + Send>>,
 }
 
 impl GitOps {
-    pub fn new(repo_dir: String, worker_count: usize, work_dir: String) -> Result<GitOps> {
+    pub fn new(
+        repo_dir: String,
+        worker_count: usize,
+        work_dir: String,
+        branches: Vec<String>,
+        runners: Vec<Box<dyn Runner + Send>>,
+    ) -> Result<GitOps> {
         let repo = Repository::open(repo_dir)?;
         let pool = ThreadPoolBuilder::new()
             .num_threads(worker_count)
@@ -52,6 +66,8 @@ impl GitOps {
             repo,
             pool,
             workdir,
+            branches,
+            runners,
         })
     }
 
@@ -162,9 +178,15 @@ impl GitOps {
 
                 move_to_new_queue(&inbound, &outbound, &key)?;
                 let workdir = self.workdir.clone();
+                let branches = self.branches.clone();
+                let runners: Vec<Box<dyn Runner + Send>> = self
+                    .runners
+                    .iter()
+                    .map(|r| dyn_clone::clone_box(&**r))
+                    .collect();
 
                 self.pool.spawn(move || {
-                    try_do_work(series_id, workdir)
+                    try_do_work(series_id, workdir, branches, runners)
                         .unwrap_or_else(|e| error!("Boned: {}", e.to_string()))
                 });
             }
@@ -175,7 +197,12 @@ impl GitOps {
     }
 }
 
-fn try_do_work(id: u64, workdir: PathBuf) -> Result<()> {
+fn try_do_work(
+    id: u64,
+    workdir: PathBuf,
+    branches: Vec<String>,
+    runners: Vec<Box<dyn Runner + Send>>,
+) -> Result<()> {
     let key = serialize(&id)?;
     let worker_id = rayon::current_thread_index().unwrap();
     let inbound = DB.open_tree(b"awaiting git worker")?;
@@ -192,7 +219,7 @@ fn try_do_work(id: u64, workdir: PathBuf) -> Result<()> {
 
     move_to_new_queue(&inbound, &outbound, &key)?;
 
-    let result = do_work(id, workdir);
+    let result = do_work(id, workdir, &branches, &runners);
 
     match result {
         Ok(_) => {
@@ -224,11 +251,7 @@ fn apply_to_repo(repo: &Repository, mbox: &Vec<u8>) -> Result<()> {
     Ok(())
 }
 
-/// This should restore
-fn clean_and_reset(repo: &Repository, branch_name: &str) -> Result<()> {
-    // There's no git2 implementation of git clean.
-    // Hopefully resetting is enough since we check all applications before
-    // actually going through with them.
+fn get_branch_commit<'a>(repo: &'a Repository, branch_name: &str) -> Result<Commit<'a>> {
     let branch = repo.find_branch(branch_name, git2::BranchType::Local)?;
     let target = branch
         .get()
@@ -238,11 +261,42 @@ fn clean_and_reset(repo: &Repository, branch_name: &str) -> Result<()> {
 
     // Can't bubble up this error because it includes repo references
     // which don't implement Sync.
-    let target = match target {
-        Ok(c) => c,
+    match target {
+        Ok(c) => Ok(c),
         Err(_) => bail!("Couldn't get commit from {}", branch_name),
-    };
+    }
+}
+
+/// Find the commit from a `base-commit:` trailer, if we have it.
+fn find_base_commit<'a>(repo: &'a Repository, base: &str) -> Option<Commit<'a>> {
+    repo.revparse_single(base)
+        .ok()?
+        .peel(ObjectType::Commit)
+        .ok()?
+        .into_commit()
+        .ok()
+}
+
+/// Look for a `base-commit:` trailer like `git format-patch --base` makes.
+/// It's in the cover letter if there is one, otherwise after the diff in the patch.
+fn get_base_commit_trailer(mails: &[&[u8]]) -> Option<String> {
+    mails.iter().find_map(|mail| {
+        String::from_utf8_lossy(mail).lines().find_map(|line| {
+            let base = line.strip_prefix("base-commit:")?.trim();
+            if !base.is_empty() && base.chars().all(|c| c.is_ascii_hexdigit()) {
+                Some(base.to_string())
+            } else {
+                None
+            }
+        })
+    })
+}
 
+/// This should restore the worktree to a pristine state on top of `target`.
+fn clean_and_reset(repo: &Repository, target: &Commit) -> Result<()> {
+    // There's no git2 implementation of git clean.
+    // Hopefully resetting is enough since we check all applications before
+    // actually going through with them.
     let mut cb = CheckoutBuilder::new();
     cb.force();
     cb.remove_untracked(true);
@@ -333,47 +387,195 @@ fn git_binary_apply_mbox(workdir: &Path, mbox: &Vec<u8>) -> Result<()> {
     Ok(())
 }
 
-fn do_work(id: u64, workdir: PathBuf) -> Result<()> {
-    let key = serialize(&id)?;
-    let worker_id = rayon::current_thread_index().unwrap();
-    let my_tree = DB.open_tree(format!("git worker {}", worker_id))?;
+/// One patch out of a series mbox, with everything we need to commit it
+/// the way the submitter sent it.
+struct MboxPatch {
+    author: String,
+    email: String,
+    date: Option<Time>,
+    subject: String,
+    body: String,
+    diff: Vec<u8>,
+}
 
-    // We (hopefully) now have exclusive access to the worktree.
-    let mut worktree_path = workdir.clone();
-    worktree_path.push(format!("snowpatch{}", worker_id));
-    let mut repo = Repository::open(&worktree_path)?;
+impl MboxPatch {
+    /// The commit message as sent, plus a trailer saying what we applied it on.
+    fn message(&self, base_name: &str) -> String {
+        let trailer = format!("Snowpatch-Base: {}\n", base_name);
 
-    clean_and_reset(&repo, "master")?;
+        if self.body.is_empty() {
+            return format!("{}\n\n{}", self.subject, trailer);
+        }
 
-    let mbox_url: String = deserialize(&my_tree.get(&key)?.unwrap())?;
-    let mbox_url = Url::parse(&mbox_url)?;
+        // Join the existing trailers (i.e. Signed-off-by) if the body ends with them.
+        let last_paragraph = self.body.rsplit("\n\n").next().unwrap_or_default();
+        let ends_with_trailers = last_paragraph
+            .lines()
+            .all(|line| match line.split_once(": ") {
+                Some((token, _)) => !token.is_empty() && !token.contains(' '),
+                None => false,
+            });
+
+        if ends_with_trailers {
+            format!("{}\n\n{}\n{}", self.subject, self.body, trailer)
+        } else {
+            format!("{}\n\n{}\n\n{}", self.subject, self.body, trailer)
+        }
+    }
+}
 
-    let mbox = download_file(&mbox_url)?;
+/// Split an mbox into individual mails, like `git mailsplit`.
+/// A new mail starts at any "From " line at the start of the file or after a blank line.
+fn split_mbox(mbox: &[u8]) -> Vec<&[u8]> {
+    let mut mails: Vec<&[u8]> = vec![];
+    let mut start: Option<usize> = None;
+    let mut pos = 0;
+    let mut prev_blank = true;
+
+    for line in mbox.split_inclusive(|&b| b == b'\n') {
+        if prev_blank && line.starts_with(b"From ") {
+            if let Some(start) = start {
+                mails.push(&mbox[start..pos]);
+            }
+            start = Some(pos);
+        }
+        prev_blank = line == b"\n" || line == b"\r\n";
+        pos += line.len();
+    }
 
-    // If the libgit2 patch apply fails, we can fall back to use the binary
-    match apply_to_repo(&repo, &mbox) {
-        Ok(_) => {
-            debug!("Applying series {} through libgit2", id);
+    match start {
+        Some(start) => mails.push(&mbox[start..]),
+        // A lone mail without an mbox separator
+        None if !mbox.is_empty() => mails.push(mbox),
+        None => {}
+    }
+
+    mails
+}
+
+/// Days since the epoch for a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// Parse an RFC 2822 mail date, i.e. `Tue, 5 Sep 2023 16:13:24 +1000`.
+/// libgit2 can't do this for us, and we don't want a whole date library for it.
+fn parse_mail_date(date: &str) -> Option<Time> {
+    // The day of the week is optional and we don't need it.
+    let date = match date.split_once(',') {
+        Some((_, rest)) => rest,
+        None => date,
+    };
+    let mut parts = date.split_whitespace();
+
+    let day: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = match parts.next()?.to_ascii_lowercase().as_str() {
+        "jan" => 1,
+        "feb" => 2,
+        "mar" => 3,
+        "apr" => 4,
+        "may" => 5,
+        "jun" => 6,
+        "jul" => 7,
+        "aug" => 8,
+        "sep" => 9,
+        "oct" => 10,
+        "nov" => 11,
+        "dec" => 12,
+        _ => return None,
+    };
+    let year: i64 = match parts.next()?.parse().ok()? {
+        y if y < 50 => y + 2000,
+        y if y < 1000 => y + 1900,
+        y => y,
+    };
+
+    let mut time = parts.next()?.split(':');
+    let hours: i64 = time.next()?.parse().ok()?;
+    let minutes: i64 = time.next()?.parse().ok()?;
+    let seconds: i64 = match time.next() {
+        Some(s) => s.parse().ok()?,
+        None => 0,
+    };
+
+    // Named zones other than UTC are obsolete, treat them as UTC like git does.
+    let offset: i64 = match parts.next() {
+        Some(zone) if zone.len() == 5 && (zone.starts_with('+') || zone.starts_with('-')) => {
+            let hhmm: i64 = zone[1..].parse().ok()?;
+            let offset = (hhmm / 100) * 60 + hhmm % 100;
+            if zone.starts_with('-') {
+                -offset
+            } else {
+                offset
+            }
         }
-        Err(_) => {
-            // There's a disappointingly large chance that a series that won't
-            // apply with git2 will actually apply just fine with the binary.
-            // So let's do that instead.
-            warn!("Series {} failed to apply through libgit2, trying binary...", id);
-            drop(repo);
-            git_binary_apply_mbox(&worktree_path, &mbox)?;
-            repo = Repository::open(&worktree_path)?;
+        _ => 0,
+    };
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hours * 3600 + minutes * 60 + seconds
+        - offset * 60;
+
+    Some(Time::new(seconds, offset as i32))
+}
+
+/// Pull the author, subject, body and diff out of a single mail.
+/// libgit2 doesn't know anything about mail, so we use `git mailinfo` just like `git am` does.
+/// It needs somewhere to write the message and diff to, that's what `scratch_dir` is for.
+fn git_binary_mailinfo(workdir: &Path, scratch_dir: &Path, mail: &[u8]) -> Result<MboxPatch> {
+    let msg_path = scratch_dir.join("snowpatch-msg");
+    let patch_path = scratch_dir.join("snowpatch-patch");
+
+    let mut git_process = Command::new("git")
+        .arg("mailinfo")
+        .arg(&msg_path)
+        .arg(&patch_path)
+        .current_dir(workdir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Couldn't invoke git binary")?;
+
+    let mut stdin = git_process.stdin.take().context("Couldn't open stdin")?;
+    stdin.write_all(mail)?;
+    drop(stdin);
+    let output = git_process.wait_with_output()?;
+
+    if !output.status.success() {
+        bail!("git mailinfo couldn't make sense of a patch");
+    }
+
+    let mut patch = MboxPatch {
+        author: String::new(),
+        email: String::new(),
+        date: None,
+        subject: String::new(),
+        body: fs::read_to_string(&msg_path)?.trim().to_string(),
+        diff: fs::read(&patch_path)?,
+    };
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        match line.split_once(": ") {
+            Some(("Author", author)) => patch.author = author.to_string(),
+            Some(("Email", email)) => patch.email = email.to_string(),
+            Some(("Subject", subject)) => patch.subject = subject.to_string(),
+            Some(("Date", date)) => patch.date = parse_mail_date(date),
+            _ => {}
         }
     }
 
-    let sig = repo.signature()?;
+    let _ = fs::remove_file(&msg_path);
+    let _ = fs::remove_file(&patch_path);
 
-    let url_prefix: String = String::from_utf8_lossy(
-        &DB.get("patchwork series link prefix")?
-            .context("Couldn't find Patchwork series link prefix in database")?,
-    )
-    .to_string();
-    let msg = format!("From patchwork series {0}\n\n{1}{0}", &id, url_prefix);
+    Ok(patch)
+}
+
+fn get_head_commit(repo: &Repository) -> Result<Commit<'_>> {
     let head_commit = repo
         .head()?
         .resolve()?
@@ -382,20 +584,172 @@ fn do_work(id: u64, workdir: PathBuf) -> Result<()> {
 
     // Can't bubble up this error because it includes repo references
     // which don't implement Sync.
-    let head_commit = match head_commit {
+    match head_commit {
+        Ok(c) => Ok(c),
+        Err(_) => bail!("Couldn't get HEAD commit"),
+    }
+}
+
+/// Reset the worktree to `base_id` and commit each patch on top of it.
+/// `base_name` is what we tell people the series was applied on.
+fn apply_series(
+    id: u64,
+    worktree_path: &Path,
+    mails: &[&[u8]],
+    base_name: &str,
+    base_id: Oid,
+) -> Result<()> {
+    let mut repo = Repository::open(worktree_path)?;
+
+    let base = match repo.find_commit(base_id) {
         Ok(c) => c,
-        Err(_) => bail!("Couldn't get HEAD commit on {}", &id),
+        Err(_) => bail!("Couldn't find base commit {}", base_id),
     };
+    clean_and_reset(&repo, &base)?;
+    drop(base);
+
+    let committer = repo.signature()?;
+    let scratch_dir = repo.path().to_path_buf();
+    let mut applied = 0;
+
+    // One commit per patch, just like `git am`, so the branch looks like what was sent.
+    for (i, mail) in mails.iter().enumerate() {
+        let patch = git_binary_mailinfo(worktree_path, &scratch_dir, mail)?;
+
+        // Cover letters and the like don't have anything to apply.
+        if patch.diff.is_empty() {
+            debug!("Skipping mail {} of series {} with no diff", i + 1, id);
+            continue;
+        }
+
+        // If the libgit2 patch apply fails, we can fall back to use the binary
+        match apply_to_repo(&repo, &patch.diff) {
+            Ok(_) => {
+                debug!("Applying patch {} of series {} through libgit2", i + 1, id);
+            }
+            Err(_) => {
+                // There's a disappointingly large chance that a series that won't
+                // apply with git2 will actually apply just fine with the binary.
+                // So let's do that instead.
+                warn!(
+                    "Patch {} of series {} failed to apply through libgit2, trying binary...",
+                    i + 1,
+                    id
+                );
+                drop(repo);
+                git_binary_apply_mbox(worktree_path, &patch.diff)?;
+                repo = Repository::open(worktree_path)?;
+            }
+        }
+
+        let author = Signature::new(
+            &patch.author,
+            &patch.email,
+            &patch.date.unwrap_or(committer.when()),
+        )?;
+        let head_commit = get_head_commit(&repo)
+            .with_context(|| format!("Couldn't get HEAD commit on {}", &id))?;
+
+        // git2 really sucks.
+        let _commit_id = repo.commit(
+            Some("HEAD"),
+            &author,
+            &committer,
+            &patch.message(base_name),
+            &repo.find_tree(repo.index()?.write_tree()?)?,
+            &[&head_commit],
+        )?;
+        applied += 1;
+    }
+
+    if applied == 0 {
+        bail!("Series {} didn't have any patches in it", id);
+    }
+
+    Ok(())
+}
+
+fn do_work(
+    id: u64,
+    workdir: PathBuf,
+    branches: &[String],
+    worktree_runners: &[Box<dyn Runner + Send>],
+) -> Result<()> {
+    let key = serialize(&id)?;
+    let worker_id = rayon::current_thread_index().unwrap();
+    let my_tree = DB.open_tree(format!("git worker {}", worker_id))?;
+
+    // We (hopefully) now have exclusive access to the worktree.
+    let mut worktree_path = workdir.clone();
+    worktree_path.push(format!("snowpatch{}", worker_id));
+    let repo = Repository::open(&worktree_path)?;
 
-    // git2 really sucks.
-    let _commit_id = repo.commit(
-        Some("HEAD"),
-        &sig,
-        &sig,
-        &msg,
-        &repo.find_tree(repo.index()?.write_tree()?)?,
-        &[&head_commit],
-    )?;
+    let mbox_url: String = deserialize(&my_tree.get(&key)?.unwrap())?;
+    let mbox_url = Url::parse(&mbox_url)?;
+
+    let mbox = download_file(&mbox_url)?;
+
+    // The cover letter isn't in the series mbox, but it's where base-commit usually is.
+    let cover_letter = match DB.open_tree(b"cover letters")?.get(&key)? {
+        Some(url) => {
+            let url: String = deserialize(&url)?;
+            Some(download_file(&Url::parse(&url)?)?)
+        }
+        None => None,
+    };
+
+    let mails = split_mbox(&mbox);
+    let base_mails: Vec<&[u8]> = cover_letter
+        .as_deref()
+        .into_iter()
+        .chain(mails.iter().copied())
+        .collect();
+
+    // Where we're going to try applying the series, in order.
+    // If the submitter told us what they based it on, that's all we try.
+    let mut bases: Vec<(String, Oid)> = vec![];
+    if let Some(base) = get_base_commit_trailer(&base_mails) {
+        match find_base_commit(&repo, &base) {
+            Some(commit) => {
+                info!("Series {} is based on {}", id, base);
+                bases.push((base.chars().take(12).collect(), commit.id()));
+            }
+            None => warn!(
+                "Series {} is based on {} which we don't have, trying {:?}",
+                id, base, branches
+            ),
+        }
+    }
+    if bases.is_empty() {
+        for branch in branches {
+            match get_branch_commit(&repo, branch) {
+                Ok(commit) => bases.push((branch.clone(), commit.id())),
+                Err(e) => warn!("Couldn't find base branch {}: {}", branch, e),
+            }
+        }
+    }
+    drop(repo);
+
+    let mut landed: Option<(String, Oid)> = None;
+    for (base_name, base_id) in bases {
+        match apply_series(id, &worktree_path, &mails, &base_name, base_id) {
+            Ok(_) => {
+                landed = Some((base_name, base_id));
+                break;
+            }
+            Err(e) => warn!("Series {} doesn't apply on {}: {}", id, base_name, e),
+        }
+    }
+    let (base_name, base_id) =
+        landed.with_context(|| format!("Series {} didn't apply on any base", id))?;
+
+    info!("Series {} applied on {}", id, base_name);
+    DB.open_tree(b"series base")?
+        .insert(id.to_string(), base_id.to_string().as_bytes())?;
+    DB.open_tree(b"series branch")?
+        .insert(id.to_string(), base_name.as_bytes())?;
+
+    let repo = Repository::open(&worktree_path)?;
 
     let remote_list_tree = DB.open_tree(b"remotes to push to")?;
     let runners = db_collect_string_values(remote_list_tree.iter())?;
@@ -429,6 +783,16 @@ fn do_work(id: u64, workdir: PathBuf) -> Result<()> {
         runner_queue_tree.insert(&id.to_string(), b"new")?;
     }
 
+    // These have to finish before we give up the worktree, so run them here.
+    let worktree_url = Url::from_file_path(fs::canonicalize(&worktree_path)?)
+        .map_err(|_| anyhow::Error::msg("Couldn't make a URL for the worktree"))?;
+    for runner in worktree_runners {
+        runner.start_work(&format!("snowpatch/{}", &id), Some(&worktree_url))?;
+
+        let runner_queue_tree = DB.open_tree(format!("{} queue", runner.get_handle()))?;
+        runner_queue_tree.insert(id.to_string(), b"new")?;
+    }
+
     Ok(())
 }
 
@@ -445,4 +809,96 @@ mod tests {
         mbox.read_to_end(&mut buffer).unwrap();
         git_binary_apply_mbox(&PathBuf::from("/home/ruscur/code/linux"), &buffer).unwrap();
     }
+
+    static TEST_MBOX: &str = "From git@z Thu Jan  1 00:00:00 1970
+Subject: [PATCH 1/2] foo: add foo
+From: =?utf-8?q?J=C3=B6rg_Tester?= <jorg@example.com>
+Date: Tue, 05 Sep 2023 16:13:24 +1000
+
+Foo is missing, add it.
+
+Signed-off-by: Jorg Tester <jorg@example.com>
+---
+ foo | 1 +
+ 1 file changed, 1 insertion(+)
+
+diff --git a/foo b/foo
+new file mode 100644
+--- /dev/null
++++ b/foo
+@@ -0,0 +1 @@
++foo
+
+From git@z Thu Jan  1 00:00:00 1970
+Subject: [PATCH 2/2] foo: more foo
+From: Jorg Tester <jorg@example.com>
+Date: Tue, 05 Sep 2023 16:13:25 +1000
+
+---
+diff --git a/foo b/foo
+--- a/foo
++++ b/foo
+@@ -1 +1,2 @@
+ foo
++foo
+";
+
+    #[test]
+    fn test_split_mbox() {
+        let mails = split_mbox(TEST_MBOX.as_bytes());
+        assert_eq!(mails.len(), 2);
+        assert!(mails[1].starts_with(b"From git@z"));
+        assert_eq!(mails.concat(), TEST_MBOX.as_bytes());
+    }
+
+    #[test]
+    fn test_get_base_commit_trailer() {
+        let mails = split_mbox(TEST_MBOX.as_bytes());
+        assert_eq!(get_base_commit_trailer(&mails), None);
+
+        let cover = b"Subject: [PATCH 0/2] foo\n\nbase-commit: 0123456789abcdef\n-- \n2.41.0\n";
+        let with_cover: Vec<&[u8]> = vec![cover, mails[0], mails[1]];
+        assert_eq!(
+            get_base_commit_trailer(&with_cover),
+            Some("0123456789abcdef".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_mail_date() {
+        let time = parse_mail_date("Tue, 05 Sep 2023 16:13:24 +1000").unwrap();
+        assert_eq!(time.seconds(), 1693894404);
+        assert_eq!(time.offset_minutes(), 600);
+
+        let time = parse_mail_date("1 Jan 1970 00:00 -0130").unwrap();
+        assert_eq!(time.seconds(), 5400);
+        assert_eq!(time.offset_minutes(), -90);
+
+        assert!(parse_mail_date("yesterday").is_none());
+    }
+
+    #[test]
+    fn test_git_binary_mailinfo() -> Result<()> {
+        let scratch_dir = std::env::temp_dir();
+        let mails = split_mbox(TEST_MBOX.as_bytes());
+        let patch = git_binary_mailinfo(&scratch_dir, &scratch_dir, mails[0])?;
+
+        assert_eq!(patch.author, "Jörg Tester");
+        assert_eq!(patch.email, "jorg@example.com");
+        assert_eq!(patch.subject, "foo: add foo");
+        assert_eq!(
+            patch.message("next"),
+            "foo: add foo\n\nFoo is missing, add it.\n\nSigned-off-by: Jorg Tester <jorg@example.com>\nSnowpatch-Base: next\n"
+        );
+
+        let patch = git_binary_mailinfo(&scratch_dir, &scratch_dir, mails[1])?;
+        assert_eq!(
+            patch.message("next"),
+            "foo: more foo\n\nSnowpatch-Base: next\n"
+        );
+        assert!(String::from_utf8_lossy(&patch.diff).contains("diff --git a/foo b/foo"));
+        assert_eq!(Diff::from_buffer(&patch.diff)?.deltas().len(), 1);
+
+        Ok(())
+    }
 }
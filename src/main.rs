@@ -34,9 +34,9 @@ extern crate url;
 extern crate ureq;
 use std::{
     fmt::Display,
+    path::PathBuf,
     thread,
     time::{Duration, Instant},
-    path::PathBuf,
 };
 use ureq::{Agent, AgentBuilder};
 
@@ -124,15 +124,28 @@ fn main() -> Result<()> {
         .timeout_write(Duration::from_secs(30))
         .build();
 
-    let git = GitOps::new(config.git.repo, config.git.workers, config.git.workdir)?;
+    let runners: Vec<Box<dyn runner::Runner + Send>> = runner::init(config.runners, agent.clone())?;
+
+    // The git workers need their own copies of any runners that use the worktree.
+    let worktree_runners: Vec<Box<dyn runner::Runner + Send>> = runners
+        .iter()
+        .filter(|r| r.needs_worktree())
+        .map(|r| dyn_clone::clone_box(&**r))
+        .collect();
+
+    let git = GitOps::new(
+        config.git.repo,
+        config.git.workers,
+        config.git.workdir,
+        config.git.branches,
+        worktree_runners,
+    )?;
 
     rayon::spawn(move || {
         git.init_worktrees().unwrap();
         git.ingest().unwrap();
     });
 
-    let runners: Vec<Box<dyn runner::Runner + Send>> = runner::init(config.runners, agent.clone())?;
-
     for r in runners {
         rayon::spawn(|| {
             runner::new_job_watcher(r).unwrap();
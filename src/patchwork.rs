@@ -335,6 +335,7 @@ pub struct CoverLetter {
     pub msgid: String,
     pub name: String,
     pub url: Url,
+    pub mbox: Option<Url>, // Patchwork 2.2+ only
 }
 
 // /api/1.2/series/
@@ -6,6 +6,7 @@ use crate::DB;
 use anyhow::{bail, Result};
 use dyn_clone::DynClone;
 use github::GitHubActions;
+use local::LocalRunner;
 use log::{debug, error, trace};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
@@ -16,6 +17,7 @@ use ureq::Agent;
 use url::Url;
 
 pub mod github;
+pub mod local;
 
 pub trait Runner: DynClone {
     fn get_handle(&self) -> String;
@@ -25,6 +27,11 @@ pub trait Runner: DynClone {
     fn get_progress(&self, branch_name: &String, url: Option<&Url>) -> Result<Vec<RunnerResult>>;
     /// Assume this can be run at any time (i.e. other fatal failures) to clean up all state, local & remote.
     fn clean_up(&self, branch_name: &String, url: Option<&Url>) -> Result<()>;
+    /// Runners that test inside the git worktree get started by the git worker
+    /// with a file URL to the worktree, instead of by new_job_watcher().
+    fn needs_worktree(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
@@ -63,6 +70,9 @@ pub fn init(config: Vec<RunnerConfig>, agent: Agent) -> Result<Vec<Box<dyn Runne
 
                 Trigger::Manual { data: _ } => todo!(),
             },
+            RunnerConfig::Local { commands, log_dir } => {
+                runners.push(Box::new(LocalRunner::new(commands, log_dir)?));
+            }
         }
     }
 
@@ -86,7 +96,10 @@ pub fn new_job_watcher(runner: Box<dyn Runner + Send>) -> Result<()> {
                 remote_branch_name
             );
 
-            runner.start_work(&remote_branch_name, None)?;
+            // Worktree runners were already started by the git worker.
+            if !runner.needs_worktree() {
+                runner.start_work(&remote_branch_name, None)?;
+            }
             move_to_new_queue(&inbound, &outbound, &key)?;
             trace!("new_job_watcher() started work & moved queue");
 
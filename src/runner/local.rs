@@ -0,0 +1,338 @@
+// Runner implementation for commands run on the snowpatch host itself.
+// Commands run in the git worker's worktree, so start_work() is called by
+// the git worker while it still owns the worktree, rather than by
+// new_job_watcher().  Results are kept in the database until dispatched.
+use anyhow::{bail, Context, Error, Result};
+use log::{debug, info, warn};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+use super::*;
+use crate::config::LocalCommand;
+
+#[derive(Clone)]
+pub struct LocalRunner {
+    commands: Vec<LocalCommand>,
+    log_dir: Option<PathBuf>,
+}
+
+/// What happened when we ran a command.
+struct CommandOutput {
+    status: Option<ExitStatus>, // None if we had to kill it
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    duration: Duration,
+}
+
+impl LocalRunner {
+    pub fn new(commands: Vec<LocalCommand>, log_dir: Option<PathBuf>) -> Result<LocalRunner> {
+        if commands.is_empty() {
+            bail!("Local runner needs at least one command");
+        }
+
+        if let Some(dir) = &log_dir {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Couldn't create log directory {}", dir.display()))?;
+        }
+
+        Ok(LocalRunner { commands, log_dir })
+    }
+
+    fn results_key(branch_name: &str, job_name: &str) -> String {
+        format!("{} {}", branch_name, job_name)
+    }
+
+    /// Run a command to completion, killing it if it goes over its timeout.
+    fn run_command(cmd: &LocalCommand, dir: &Path) -> Result<CommandOutput> {
+        let start = Instant::now();
+        let mut child: Child = Command::new("sh")
+            .arg("-c")
+            .arg(&cmd.command)
+            .current_dir(dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // Own process group, so we can kill everything it spawns on timeout.
+            .process_group(0)
+            .spawn()
+            .with_context(|| format!("Couldn't spawn {}", cmd.name))?;
+
+        // Drain the pipes as we go, otherwise chatty commands fill them up and hang.
+        let mut stdout = child.stdout.take().context("Couldn't open stdout")?;
+        let mut stderr = child.stderr.take().context("Couldn't open stderr")?;
+        let stdout_reader = thread::spawn(move || {
+            let mut buf: Vec<u8> = vec![];
+            let _ = stdout.read_to_end(&mut buf);
+            buf
+        });
+        let stderr_reader = thread::spawn(move || {
+            let mut buf: Vec<u8> = vec![];
+            let _ = stderr.read_to_end(&mut buf);
+            buf
+        });
+
+        let timeout = cmd.timeout.map(Duration::from_secs);
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break Some(status);
+            }
+            if let Some(timeout) = timeout {
+                if start.elapsed() >= timeout {
+                    warn!(
+                        "{} timed out after {}s, killing it",
+                        cmd.name,
+                        timeout.as_secs()
+                    );
+                    // Anything left holding our pipes open would hang the readers.
+                    let _ = Command::new("kill")
+                        .arg("-KILL")
+                        .arg(format!("-{}", child.id()))
+                        .status();
+                    child.kill()?;
+                    child.wait()?;
+                    break None;
+                }
+            }
+            thread::sleep(Duration::from_millis(250));
+        };
+
+        let stdout = stdout_reader
+            .join()
+            .map_err(|_| Error::msg("stdout reader panicked"))?;
+        let stderr = stderr_reader
+            .join()
+            .map_err(|_| Error::msg("stderr reader panicked"))?;
+
+        Ok(CommandOutput {
+            status,
+            stdout,
+            stderr,
+            duration: start.elapsed(),
+        })
+    }
+
+    /// Write everything the command printed to the log directory, if we have one.
+    fn write_log(
+        &self,
+        branch_name: &str,
+        job_name: &str,
+        output: &CommandOutput,
+    ) -> Result<Option<Url>> {
+        let dir = match &self.log_dir {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+
+        // snowpatch/1234 becomes snowpatch-1234
+        let mut path = fs::canonicalize(dir)?;
+        path.push(format!(
+            "{}-{}.log",
+            branch_name.replace('/', "-"),
+            job_name.replace('/', "-")
+        ));
+
+        let mut file = File::create(&path)?;
+        file.write_all(b"==== stdout ====\n")?;
+        file.write_all(&output.stdout)?;
+        file.write_all(b"\n==== stderr ====\n")?;
+        file.write_all(&output.stderr)?;
+
+        let url = Url::from_file_path(&path)
+            .map_err(|_| Error::msg(format!("Bad log path {}", path.display())))?;
+
+        Ok(Some(url))
+    }
+
+    fn output_to_runner_result(
+        cmd: &LocalCommand,
+        output: &CommandOutput,
+        url: Option<Url>,
+    ) -> RunnerResult {
+        let secs = output.duration.as_secs();
+
+        let (outcome, description) = match output.status {
+            Some(status) if status.success() => (
+                TestState::Success,
+                format!("{} passed in {}s.", cmd.name, secs),
+            ),
+            Some(status) => {
+                let code = match status.code() {
+                    Some(code) => format!("exit code {}", code),
+                    None => String::from("a signal"),
+                };
+                // The last thing printed to stderr is usually the most useful.
+                let last_line = String::from_utf8_lossy(&output.stderr)
+                    .lines()
+                    .rev()
+                    .find(|l| !l.trim().is_empty())
+                    .map(|l| l.trim().to_string());
+                let description = match last_line {
+                    Some(line) => format!(
+                        "{} failed with {} after {}s: {}",
+                        cmd.name, code, secs, line
+                    ),
+                    None => format!("{} failed with {} after {}s.", cmd.name, code, secs),
+                };
+                (TestState::Fail, description)
+            }
+            None => (
+                TestState::Fail,
+                format!("{} timed out after {}s.", cmd.name, secs),
+            ),
+        };
+
+        RunnerResult {
+            name: cmd.name.clone(),
+            state: JobState::Completed,
+            outcome,
+            url,
+            description: Some(description),
+        }
+    }
+}
+
+impl Runner for LocalRunner {
+    fn get_handle(&self) -> String {
+        "local".to_string()
+    }
+
+    fn needs_worktree(&self) -> bool {
+        true
+    }
+
+    /// `url` has to be a file URL pointing at the worktree to test in.
+    /// Doesn't return until every command has finished.
+    fn start_work(&self, branch_name: &String, url: Option<&Url>) -> Result<()> {
+        let dir = url
+            .context("Local runner needs a worktree to run in")?
+            .to_file_path()
+            .map_err(|_| Error::msg("Local runner needs a file URL for the worktree"))?;
+        let tree = DB.open_tree(b"local results")?;
+
+        for cmd in &self.commands {
+            info!(
+                "Running {} for {} in {}",
+                cmd.name,
+                branch_name,
+                dir.display()
+            );
+
+            let result = match LocalRunner::run_command(cmd, &dir) {
+                Ok(output) => {
+                    debug!(
+                        "{} stdout: {}",
+                        cmd.name,
+                        String::from_utf8_lossy(&output.stdout)
+                    );
+                    debug!(
+                        "{} stderr: {}",
+                        cmd.name,
+                        String::from_utf8_lossy(&output.stderr)
+                    );
+                    let url = self.write_log(branch_name, &cmd.name, &output)?;
+                    LocalRunner::output_to_runner_result(cmd, &output, url)
+                }
+                Err(e) => {
+                    error!("Couldn't run {}: {}", cmd.name, e);
+                    RunnerResult {
+                        name: cmd.name.clone(),
+                        state: JobState::Failed,
+                        outcome: TestState::Warning,
+                        url: None,
+                        description: Some(format!(
+                            "{} couldn't be run, not the patch's fault",
+                            cmd.name
+                        )),
+                    }
+                }
+            };
+
+            tree.insert(
+                LocalRunner::results_key(branch_name, &cmd.name).as_bytes(),
+                bincode::serialize(&result)?,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn get_progress(&self, branch_name: &String, _url: Option<&Url>) -> Result<Vec<RunnerResult>> {
+        let tree = DB.open_tree(b"local results")?;
+        let mut results: Vec<RunnerResult> = vec![];
+
+        for cmd in &self.commands {
+            if let Some(value) = tree.get(LocalRunner::results_key(branch_name, &cmd.name))? {
+                results.push(bincode::deserialize(&value)?);
+            }
+        }
+
+        Ok(results)
+    }
+
+    fn clean_up(&self, branch_name: &String, _url: Option<&Url>) -> Result<()> {
+        let tree = DB.open_tree(b"local results")?;
+
+        for cmd in &self.commands {
+            tree.remove(LocalRunner::results_key(branch_name, &cmd.name))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn command(command: &str, timeout: Option<u64>) -> LocalCommand {
+        LocalCommand {
+            name: "test".to_string(),
+            command: command.to_string(),
+            timeout,
+        }
+    }
+
+    #[test]
+    fn command_success() -> Result<()> {
+        let cmd = command("echo hello", None);
+        let output = LocalRunner::run_command(&cmd, &env::temp_dir())?;
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\n");
+
+        let result = LocalRunner::output_to_runner_result(&cmd, &output, None);
+        assert_eq!(result.state, JobState::Completed);
+        assert_eq!(result.outcome, TestState::Success);
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_failure() -> Result<()> {
+        let cmd = command("echo oh no >&2; exit 3", None);
+        let output = LocalRunner::run_command(&cmd, &env::temp_dir())?;
+
+        let result = LocalRunner::output_to_runner_result(&cmd, &output, None);
+        assert_eq!(result.outcome, TestState::Fail);
+        let description = result.description.unwrap();
+        assert!(description.contains("exit code 3"));
+        assert!(description.ends_with("oh no"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_timeout() -> Result<()> {
+        let cmd = command("sleep 30", Some(1));
+        let output = LocalRunner::run_command(&cmd, &env::temp_dir())?;
+        assert!(output.status.is_none());
+
+        let result = LocalRunner::output_to_runner_result(&cmd, &output, None);
+        assert_eq!(result.outcome, TestState::Fail);
+
+        Ok(())
+    }
+}
@@ -40,6 +40,12 @@ impl Watchcat {
 
         // TODO need consolidation between this and the filters
         if checks.is_empty() || true {
+            // The git worker wants this for base-commit trailers, so it has to be there first.
+            if let Some(mbox) = series.cover_letter.as_ref().and_then(|c| c.mbox.as_ref()) {
+                DB.open_tree(b"cover letters")?
+                    .insert(bincode::serialize(&series.id)?, bincode::serialize(mbox)?)?;
+            }
+
             let tree = DB.open_tree(b"needs testing")?;
 
             // TODO here the value would be more useful information, probably.